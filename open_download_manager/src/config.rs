@@ -8,6 +8,19 @@ pub struct Config {
     pub max_retries: u8,
     pub num_threads: usize,
     pub default_output_path: String,
+    pub retry_base_ms: u64,
+    pub retry_cap_secs: u64,
+    /// Global cap on concurrently in-flight segment requests, across every
+    /// queued download, enforced by the manager's `Scheduler`.
+    pub max_concurrent: usize,
+    /// Forces the archive format used by `download_file_segmented_extract`
+    /// ("tar.gz", "tar.bz2", or "tar.lz4"), bypassing URL/Content-Type
+    /// detection for servers that expose neither.
+    pub archive_kind_override: Option<String>,
+    /// Size of each claimable unit in `download_file_work_stealing`. Smaller
+    /// blocks keep fast connections saturated longer but add per-block
+    /// request overhead.
+    pub block_size_bytes: u64,
 }
 
 
@@ -19,6 +32,11 @@ impl Default for Config {
             num_threads: 4,
             default_output_path: "".to_string(),
             //default_output_path: "/Volumes/WD ELEMENTS/ODM/ODM/open_download_manager/".to_string(),
+            retry_base_ms: 500,
+            retry_cap_secs: 30,
+            max_concurrent: 16,
+            archive_kind_override: None,
+            block_size_bytes: 4 * 1024 * 1024,
         }
     }
 }
@@ -1,14 +1,18 @@
 use crate::config::Config;
 use crate::download::manager::download_file_segmented;
 use crate::download::progress::SegmentedProgressTracker;
+use crate::state::metadata::Checksum;
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 use std::sync::atomic::{AtomicBool};
-use std::thread::JoinHandle;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use std::sync::atomic::Ordering;
 
+use reqwest::blocking::Client;
+
 #[derive(Debug, PartialEq)]
 pub enum DownloadStatus {
     Idle,
@@ -41,6 +45,16 @@ pub struct DownloadProgress {
     pub total_size: u64,
     pub percent: f64,
     pub per_segment: Vec<(usize, u64, u64)>, // segment_id, downloaded, total
+    pub extracted_bytes: u64,
+    pub extracting: bool,
+    /// Rolling bytes/sec estimate, `None` until enough samples have landed.
+    pub speed_bps: Option<f64>,
+    /// Estimated seconds remaining at the current rate, `None` when the
+    /// total size or rate isn't known yet.
+    pub eta_secs: Option<f64>,
+    /// segment_id, attempt, max_retries — segments currently backed off
+    /// after a retriable failure, so a caller can render "retrying (3/5)".
+    pub retrying: Vec<(usize, u8, u8)>,
 }
 
 pub struct DownloadTask {
@@ -54,16 +68,154 @@ pub struct DownloadTask {
     pub progress: Arc<Mutex<SegmentedProgressTracker>>,
 }
 
+/// Tracks segments that are backed off after a retriable failure, so a
+/// failed segment can be parked with a wake-deadline instead of blocking
+/// its worker on a raw `thread::sleep`.
+pub struct SleepTracker {
+    parked: Mutex<HashMap<usize, Instant>>,
+}
+
+impl SleepTracker {
+    pub fn new() -> Self {
+        Self {
+            parked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Parks `segment_id` until `delay` has elapsed, sleeping in short polls;
+    /// while parked, its deadline is visible through `parked_segments`. The
+    /// wait cannot be canceled early.
+    ///
+    /// This blocks the calling thread for the whole delay — fine for a
+    /// caller permanently dedicated to one unit of work (e.g. the
+    /// work-stealing pool's per-block retry loop), but not for a shared
+    /// pool of workers that should pick up other ready work in the
+    /// meantime. For that, use `park`/`due` instead.
+    pub fn sleep_until(&self, segment_id: usize, delay: Duration) {
+        let wake_at = Instant::now() + delay;
+        self.parked.lock().unwrap().insert(segment_id, wake_at);
+
+        loop {
+            let remaining = wake_at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(remaining.min(Duration::from_millis(200)));
+        }
+
+        self.parked.lock().unwrap().remove(&segment_id);
+    }
+
+    /// Non-blocking variant of `sleep_until`: records the wake deadline and
+    /// returns immediately, so the calling worker can go fetch other ready
+    /// work from a shared pool instead of sitting idle on this one segment.
+    pub fn park(&self, segment_id: usize, delay: Duration) {
+        let wake_at = Instant::now() + delay;
+        self.parked.lock().unwrap().insert(segment_id, wake_at);
+    }
+
+    /// True once `park`'s deadline for `segment_id` has elapsed. Removes the
+    /// bookkeeping entry as a side effect, so call this at most once per
+    /// segment per wake-up.
+    pub fn due(&self, segment_id: usize) -> bool {
+        let mut parked = self.parked.lock().unwrap();
+        match parked.get(&segment_id) {
+            Some(wake_at) if *wake_at <= Instant::now() => {
+                parked.remove(&segment_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Segment ids currently parked, along with how much longer they'll sleep.
+    pub fn parked_segments(&self) -> Vec<(usize, Duration)> {
+        let now = Instant::now();
+        self.parked
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, wake_at)| (*id, wake_at.saturating_duration_since(now)))
+            .collect()
+    }
+}
+
+/// A shared HTTP client plus a global cap on concurrently in-flight segment
+/// requests, modeled after Cargo's `Downloads`: every segment fetch (initial
+/// attempt or retry, for every queued download) acquires a token here before
+/// it sends a request, so N concurrent downloads can't open N times
+/// `num_threads` sockets with no bound.
+pub struct Scheduler {
+    client: Client,
+    max_concurrent: usize,
+    active: Mutex<usize>,
+    available: Condvar,
+}
+
+impl Scheduler {
+    pub fn new(client: Client, max_concurrent: usize) -> Self {
+        Self {
+            client,
+            max_concurrent: max_concurrent.max(1),
+            active: Mutex::new(0),
+            available: Condvar::new(),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Blocks until a concurrency token is free, then returns a guard that
+    /// releases it back to the pool on drop.
+    pub fn acquire(&self) -> SchedulerToken<'_> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.max_concurrent {
+            active = self.available.wait(active).unwrap();
+        }
+        *active += 1;
+        SchedulerToken { scheduler: self }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        *self.active.lock().unwrap()
+    }
+}
+
+pub struct SchedulerToken<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl<'a> Drop for SchedulerToken<'a> {
+    fn drop(&mut self) {
+        let mut active = self.scheduler.active.lock().unwrap();
+        *active -= 1;
+        self.scheduler.available.notify_one();
+    }
+}
+
 pub struct DownloadManager {
     pub tasks: Mutex<HashMap<String, DownloadTask>>,
     pub config: Arc<Config>,
+    pub sleep_tracker: Arc<SleepTracker>,
+    pub scheduler: Arc<Scheduler>,
 }
 
 impl DownloadManager {
     pub fn new(config: Config) -> Self {
+        let scheduler = Arc::new(Scheduler::new(
+            Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()
+                .expect("failed to build shared HTTP client"),
+            config.max_concurrent,
+        ));
+
         Self {
             tasks: Mutex::new(HashMap::new()),
             config: Arc::new(config),
+            sleep_tracker: Arc::new(SleepTracker::new()),
+            scheduler,
         }
     }
 
@@ -91,15 +243,41 @@ impl DownloadManager {
             per_segment: tracker.segments.iter()
                 .map(|(id, (downloaded, total))| (*id, *downloaded, *total))
                 .collect(),
+            extracted_bytes: tracker.extracted_bytes,
+            extracting: tracker.extracting,
+            speed_bps: tracker.speed_bps(),
+            eta_secs: tracker.eta_secs(),
+            retrying: tracker.retrying.iter()
+                .map(|(id, (attempt, max_retries))| (*id, *attempt, *max_retries))
+                .collect(),
         })
     }
 
     pub fn add_download(&self, url: String, output_path: String) -> String {
+        self.add_download_inner(url, output_path, self.config.num_threads, None)
+    }
+
+    /// Like `add_download`, but after the segments are merged the assembled
+    /// file is streamed through a hasher and compared against `checksum`;
+    /// a mismatch fails the download with a descriptive reason instead of
+    /// silently leaving corrupt bytes at `output_path`.
+    pub fn add_download_verified(&self, url: String, output_path: String, checksum: Checksum) -> String {
+        self.add_download_inner(url, output_path, self.config.num_threads, Some(checksum))
+    }
+
+    /// Like `add_download`, but splits this job into `num_threads` segments
+    /// instead of the manager's configured default — for callers (e.g.
+    /// `DownloadQueue::enqueue`) that want a per-job thread count.
+    pub fn add_download_with_threads(&self, url: String, output_path: String, num_threads: usize) -> String {
+        self.add_download_inner(url, output_path, num_threads, None)
+    }
+
+    fn add_download_inner(&self, url: String, output_path: String, num_threads: usize, expected_checksum: Option<Checksum>) -> String {
         let id = crate::download::manager::hash_url(&url);
         let pause_flag = Arc::new(AtomicBool::new(false));
         let status = Arc::new(Mutex::new(DownloadStatus::Idle));
         let progress = Arc::new(Mutex::new(SegmentedProgressTracker::new(
-            self.config.num_threads,
+            num_threads,
             0,
             0,
         )));
@@ -109,6 +287,8 @@ impl DownloadManager {
         let status_clone_download = Arc::clone(&status);
         let status_clone_set = Arc::clone(&status);
         let progress_clone = Arc::clone(&progress);
+        let sleep_tracker_clone = Arc::clone(&self.sleep_tracker);
+        let scheduler_clone = Arc::clone(&self.scheduler);
 
         let url_clone = url.clone();
         let output_path_clone = output_path.clone();
@@ -118,11 +298,118 @@ impl DownloadManager {
             match download_file_segmented(
                 &url_clone,
                 &output_path_clone,
-                config_clone.num_threads,
+                num_threads,
                 &config_clone,
                 Some(pause_flag_clone),
                 Some(status_clone_download),
                 Some(progress_clone),
+                Some(sleep_tracker_clone),
+                Some(scheduler_clone),
+                expected_checksum,
+            ) {
+                Ok(_) => *status_clone_set.lock().unwrap() = DownloadStatus::Completed,
+                Err(e) => *status_clone_set.lock().unwrap() = DownloadStatus::Failed(e.to_string()),
+            }
+        });
+
+        let task = DownloadTask {
+            id: id.clone(),
+            url,
+            output_path,
+            meta_path: format!("downloads/meta/{}.meta.json", id),
+            handles: vec![handle],
+            pause_flag,
+            status,
+            progress,
+        };
+
+        self.tasks.lock().unwrap().insert(id.clone(), task);
+        id
+    }
+
+    /// Downloads a `.tar.gz` / `.tar.bz2` / `.tar.lz4` archive and extracts
+    /// it into `target_dir` as segments arrive, instead of writing the
+    /// archive to disk and extracting afterwards.
+    pub fn add_download_extract(&self, url: String, target_dir: String) -> String {
+        let id = crate::download::manager::hash_url(&url);
+        let pause_flag = Arc::new(AtomicBool::new(false));
+        let status = Arc::new(Mutex::new(DownloadStatus::Idle));
+        let progress = Arc::new(Mutex::new(SegmentedProgressTracker::new(
+            self.config.num_threads,
+            0,
+            0,
+        )));
+
+        let config_clone = Arc::clone(&self.config);
+        let pause_flag_clone = Arc::clone(&pause_flag);
+        let status_clone_download = Arc::clone(&status);
+        let status_clone_set = Arc::clone(&status);
+        let progress_clone = Arc::clone(&progress);
+        let scheduler_clone = Arc::clone(&self.scheduler);
+
+        let url_clone = url.clone();
+        let target_dir_clone = target_dir.clone();
+
+        let handle = std::thread::spawn(move || {
+            *status_clone_download.lock().unwrap() = DownloadStatus::Downloading;
+            match crate::download::manager::download_file_segmented_extract(
+                &url_clone,
+                &target_dir_clone,
+                config_clone.num_threads,
+                &config_clone,
+                Some(pause_flag_clone),
+                Some(progress_clone),
+                Some(scheduler_clone),
+            ) {
+                Ok(_) => *status_clone_set.lock().unwrap() = DownloadStatus::Completed,
+                Err(e) => *status_clone_set.lock().unwrap() = DownloadStatus::Failed(e.to_string()),
+            }
+        });
+
+        let task = DownloadTask {
+            id: id.clone(),
+            url,
+            output_path: target_dir,
+            meta_path: format!("downloads/meta/{}.meta.json", id),
+            handles: vec![handle],
+            pause_flag,
+            status,
+            progress,
+        };
+
+        self.tasks.lock().unwrap().insert(id.clone(), task);
+        id
+    }
+
+    /// Like `add_download`, but hands out small blocks from a shared
+    /// work-stealing pool instead of one fixed range per thread, so a slow
+    /// connection can't leave the rest of `num_threads` workers idle.
+    pub fn add_download_work_stealing(&self, url: String, output_path: String) -> String {
+        let id = crate::download::manager::hash_url(&url);
+        let pause_flag = Arc::new(AtomicBool::new(false));
+        let status = Arc::new(Mutex::new(DownloadStatus::Idle));
+        let progress = Arc::new(Mutex::new(SegmentedProgressTracker::new(0, 0, 0)));
+
+        let config_clone = Arc::clone(&self.config);
+        let pause_flag_clone = Arc::clone(&pause_flag);
+        let status_clone_download = Arc::clone(&status);
+        let status_clone_set = Arc::clone(&status);
+        let progress_clone = Arc::clone(&progress);
+        let scheduler_clone = Arc::clone(&self.scheduler);
+
+        let url_clone = url.clone();
+        let output_path_clone = output_path.clone();
+
+        let handle = std::thread::spawn(move || {
+            *status_clone_download.lock().unwrap() = DownloadStatus::Downloading;
+            match crate::download::workpool::download_file_work_stealing(
+                &url_clone,
+                &output_path_clone,
+                config_clone.num_threads,
+                &config_clone,
+                Some(pause_flag_clone),
+                Some(progress_clone),
+                Some(scheduler_clone),
             ) {
                 Ok(_) => *status_clone_set.lock().unwrap() = DownloadStatus::Completed,
                 Err(e) => *status_clone_set.lock().unwrap() = DownloadStatus::Failed(e.to_string()),
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::config::Config;
+use crate::core::manager::DownloadManager;
+
+/// Semaphore-style permit pool capping how many *downloads* run
+/// concurrently, layered on top of the per-download thread count already
+/// enforced by `DownloadManager`/`Scheduler`. Without this, enqueueing a
+/// batch of URLs would spawn `num_threads` segment threads per job with no
+/// bound on the number of jobs.
+struct DownloadPermits {
+    max_concurrent: usize,
+    active: Mutex<usize>,
+    available: Condvar,
+}
+
+impl DownloadPermits {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            active: Mutex::new(0),
+            available: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.max_concurrent {
+            active = self.available.wait(active).unwrap();
+        }
+        *active += 1;
+    }
+
+    fn release(&self) {
+        let mut active = self.active.lock().unwrap();
+        *active -= 1;
+        self.available.notify_one();
+    }
+}
+
+/// Sum of every enqueued job's `DownloadProgress`, for a single "how's the
+/// whole batch doing" readout.
+#[derive(Debug, Default)]
+pub struct QueueProgress {
+    pub total_downloaded: u64,
+    pub total_size: u64,
+    pub jobs_tracked: usize,
+}
+
+/// Runs many downloads through a single `DownloadManager`, capped at
+/// `max_concurrent_downloads` running at once; the rest wait for a permit.
+///
+/// Every job shares the manager's `Scheduler`; each job's thread count is the
+/// one passed to `enqueue` — this queue bounds concurrent *jobs*, not per-job
+/// thread counts.
+pub struct DownloadQueue {
+    manager: Arc<DownloadManager>,
+    permits: Arc<DownloadPermits>,
+    job_ids: Mutex<Vec<String>>,
+}
+
+impl DownloadQueue {
+    pub fn new(config: Config, max_concurrent_downloads: usize) -> Self {
+        Self {
+            manager: Arc::new(DownloadManager::new(config)),
+            permits: Arc::new(DownloadPermits::new(max_concurrent_downloads)),
+            job_ids: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn manager(&self) -> &Arc<DownloadManager> {
+        &self.manager
+    }
+
+    /// Queues a download, split into `threads` segments — independent of the
+    /// manager's configured default, so individual jobs can be given a
+    /// different thread count. It starts as soon as a permit is free and
+    /// releases the permit once the job's own download thread(s) finish,
+    /// regardless of whether it completed, paused, or failed.
+    pub fn enqueue(&self, url: String, output_path: String, threads: usize) -> String {
+        let id = crate::download::manager::hash_url(&url);
+        self.job_ids.lock().unwrap().push(id.clone());
+
+        let manager = Arc::clone(&self.manager);
+        let permits = Arc::clone(&self.permits);
+
+        thread::spawn(move || {
+            permits.acquire();
+            let id = manager.add_download_with_threads(url, output_path, threads);
+
+            let handles = manager
+                .tasks
+                .lock()
+                .unwrap()
+                .get_mut(&id)
+                .map(|task| std::mem::take(&mut task.handles));
+
+            if let Some(handles) = handles {
+                for handle in handles {
+                    let _ = handle.join();
+                }
+            }
+
+            permits.release();
+        });
+
+        id
+    }
+
+    pub fn pause_all(&self) {
+        for id in self.job_ids.lock().unwrap().iter() {
+            self.manager.pause(id);
+        }
+    }
+
+    pub fn resume_all(&self) {
+        for id in self.job_ids.lock().unwrap().iter() {
+            self.manager.resume(id);
+        }
+    }
+
+    /// Aggregates `DownloadProgress` across every enqueued job that still
+    /// has tracked progress (removed/unknown ids are skipped).
+    pub fn progress(&self) -> QueueProgress {
+        let mut agg = QueueProgress::default();
+        for id in self.job_ids.lock().unwrap().iter() {
+            if let Some(p) = self.manager.get_progress(id) {
+                agg.total_downloaded += p.total_downloaded;
+                agg.total_size += p.total_size;
+                agg.jobs_tracked += 1;
+            }
+        }
+        agg
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use lz4_flex::frame::FrameDecoder;
+use tar::Archive;
+
+use crate::download::progress::SegmentedProgressTracker;
+
+/// Compression format of a streamed archive, detected from the URL or the
+/// response's `Content-Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    TarGz,
+    TarBz2,
+    TarLz4,
+}
+
+impl ArchiveKind {
+    /// Parses a `Config::archive_kind_override` value, for callers that know
+    /// the format up front and want to skip URL/Content-Type detection.
+    pub fn from_override(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "tar.gz" | "tgz" | "gzip" => Some(ArchiveKind::TarGz),
+            "tar.bz2" | "tbz2" | "bzip2" => Some(ArchiveKind::TarBz2),
+            "tar.lz4" | "lz4" => Some(ArchiveKind::TarLz4),
+            _ => None,
+        }
+    }
+
+    pub fn detect(url: &str, content_type: Option<&str>) -> Option<Self> {
+        let lower = url.to_ascii_lowercase();
+        if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            return Some(ArchiveKind::TarGz);
+        }
+        if lower.ends_with(".tar.bz2") || lower.ends_with(".tbz2") {
+            return Some(ArchiveKind::TarBz2);
+        }
+        if lower.ends_with(".tar.lz4") {
+            return Some(ArchiveKind::TarLz4);
+        }
+
+        match content_type {
+            Some(ct) if ct.contains("gzip") => Some(ArchiveKind::TarGz),
+            Some(ct) if ct.contains("bzip2") => Some(ArchiveKind::TarBz2),
+            Some(ct) if ct.contains("lz4") => Some(ArchiveKind::TarLz4),
+            _ => None,
+        }
+    }
+}
+
+/// An ordered byte range handed from a download worker to the extraction
+/// consumer. `offset` is the position within the archive stream, used to
+/// reassemble segments that complete out of order.
+pub struct Chunk {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+pub type ChunkSender = SyncSender<Chunk>;
+
+/// `Read` adapter over a bounded channel of ordered chunks. Chunks that
+/// arrive ahead of `next_offset` are buffered until the gap is filled, so
+/// the decoder always sees bytes in stream order even though segments are
+/// downloaded concurrently.
+struct ChunkReader {
+    rx: Receiver<Chunk>,
+    pending: BTreeMap<u64, Vec<u8>>,
+    next_offset: u64,
+    current: Vec<u8>,
+    current_pos: usize,
+}
+
+impl ChunkReader {
+    fn new(rx: Receiver<Chunk>, start_offset: u64) -> Self {
+        Self {
+            rx,
+            pending: BTreeMap::new(),
+            next_offset: start_offset,
+            current: Vec::new(),
+            current_pos: 0,
+        }
+    }
+
+    fn fill_current(&mut self) -> bool {
+        loop {
+            if let Some(bytes) = self.pending.remove(&self.next_offset) {
+                self.next_offset += bytes.len() as u64;
+                self.current = bytes;
+                self.current_pos = 0;
+                return true;
+            }
+
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.pending.insert(chunk.offset, chunk.bytes);
+                }
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.current_pos >= self.current.len() && !self.fill_current() {
+            return Ok(0);
+        }
+
+        let available = &self.current[self.current_pos..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.current_pos += n;
+        Ok(n)
+    }
+}
+
+/// Wraps a reader and records how many decompressed bytes have passed
+/// through it, so extraction progress can be reported separately from
+/// download progress.
+struct CountingReader<R> {
+    inner: R,
+    tracker: Arc<Mutex<SegmentedProgressTracker>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.tracker.lock().unwrap().update_extracted(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+/// Spawns the decode/extract consumer thread. Returns the channel download
+/// workers should push ordered chunks into and a join handle resolving to
+/// the overall unpack result.
+pub fn spawn_extractor(
+    kind: ArchiveKind,
+    start_offset: u64,
+    target_dir: &Path,
+    tracker: Arc<Mutex<SegmentedProgressTracker>>,
+    channel_capacity: usize,
+) -> (ChunkSender, thread::JoinHandle<io::Result<()>>) {
+    let (tx, rx) = sync_channel::<Chunk>(channel_capacity);
+    let target_dir: PathBuf = target_dir.to_path_buf();
+
+    let handle = thread::spawn(move || -> io::Result<()> {
+        tracker.lock().unwrap().set_extracting(true);
+        let reader = ChunkReader::new(rx, start_offset);
+        let counting = CountingReader { inner: reader, tracker: Arc::clone(&tracker) };
+
+        let result = match kind {
+            ArchiveKind::TarGz => Archive::new(GzDecoder::new(counting)).unpack(&target_dir),
+            ArchiveKind::TarBz2 => Archive::new(BzDecoder::new(counting)).unpack(&target_dir),
+            ArchiveKind::TarLz4 => Archive::new(FrameDecoder::new(counting)).unpack(&target_dir),
+        };
+
+        tracker.lock().unwrap().set_extracting(false);
+        result
+    });
+
+    (tx, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn detect_prefers_url_suffix_over_content_type() {
+        assert_eq!(ArchiveKind::detect("https://example.com/file.tar.gz", None), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("https://example.com/file.tgz", None), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("https://example.com/file.tar.bz2", None), Some(ArchiveKind::TarBz2));
+        assert_eq!(ArchiveKind::detect("https://example.com/file.tar.lz4", None), Some(ArchiveKind::TarLz4));
+        assert_eq!(ArchiveKind::detect("https://example.com/file.tar.gz", Some("application/x-bzip2")), Some(ArchiveKind::TarGz));
+    }
+
+    #[test]
+    fn from_override_accepts_known_aliases_case_insensitively() {
+        assert_eq!(ArchiveKind::from_override("TAR.GZ"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_override("tgz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_override("Bzip2"), Some(ArchiveKind::TarBz2));
+        assert_eq!(ArchiveKind::from_override("lz4"), Some(ArchiveKind::TarLz4));
+        assert_eq!(ArchiveKind::from_override("zip"), None);
+    }
+
+    #[test]
+    fn detect_falls_back_to_content_type() {
+        assert_eq!(ArchiveKind::detect("https://example.com/download", Some("application/gzip")), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("https://example.com/download", Some("application/x-bzip2")), Some(ArchiveKind::TarBz2));
+        assert_eq!(ArchiveKind::detect("https://example.com/download", None), None);
+    }
+
+    #[test]
+    fn fill_current_reassembles_out_of_order_chunks() {
+        let (tx, rx) = sync_channel::<Chunk>(4);
+        tx.send(Chunk { offset: 3, bytes: vec![3, 4, 5] }).unwrap();
+        tx.send(Chunk { offset: 0, bytes: vec![0, 1, 2] }).unwrap();
+        drop(tx);
+
+        let mut reader = ChunkReader::new(rx, 0);
+        assert!(reader.fill_current());
+        assert_eq!(reader.current, vec![0, 1, 2]);
+        assert!(reader.fill_current());
+        assert_eq!(reader.current, vec![3, 4, 5]);
+        assert!(!reader.fill_current());
+    }
+}
@@ -1,14 +1,17 @@
 use crate::config::Config;
+use crate::download::extract::{self, ArchiveKind};
 use crate::download::progress::SegmentedProgressTracker;
-use crate::download::segment::DownloadSegment;
+use crate::download::segment::{AttemptOutcome, DownloadSegment};
 use crate::download::single;
-use crate::state::metadata::{DownloadMetadata, SegmentMetadata};
-use crate::core::manager::DownloadStatus;
+use crate::state::metadata::{Checksum, DownloadMetadata, SegmentMetadata};
+use crate::core::manager::{DownloadStatus, Scheduler, SleepTracker};
 
+use std::collections::VecDeque;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::thread;
 use std::time::Duration;
 
@@ -22,19 +25,43 @@ pub fn download_file_segmented(
     external_pause_flag: Option<Arc<AtomicBool>>,
     external_status: Option<Arc<Mutex<DownloadStatus>>>,
     external_tracker: Option<Arc<Mutex<SegmentedProgressTracker>>>,
+    external_sleep_tracker: Option<Arc<SleepTracker>>,
+    external_scheduler: Option<Arc<Scheduler>>,
+    expected_checksum: Option<Checksum>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let url_hash = hash_url(url);
     let meta_path = format!("downloads/meta/{}.meta.json", url_hash);
     fs::create_dir_all("downloads/meta")?;
 
-    let mut metadata: DownloadMetadata;
+    let scheduler = external_scheduler.unwrap_or_else(|| {
+        Arc::new(Scheduler::new(
+            Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()
+                .expect("failed to build shared HTTP client"),
+            config.max_concurrent,
+        ))
+    });
 
-    if DownloadMetadata::exists(&meta_path) {
+    let resumable = if DownloadMetadata::exists(&meta_path) {
+        let candidate = DownloadMetadata::load_from_file(&meta_path)?;
+        if validate_resume(scheduler.client(), url, &candidate)? {
+            Some(candidate)
+        } else {
+            println!("⚠️ Remote resource changed since last run — discarding stale parts.");
+            discard_parts(&candidate);
+            fs::remove_file(&meta_path).ok();
+            None
+        }
+    } else {
+        None
+    };
+
+    let mut metadata: DownloadMetadata = if let Some(candidate) = resumable {
         println!("🔄 Resuming download from metadata...");
-        metadata = DownloadMetadata::load_from_file(&meta_path)?;
+        candidate
     } else {
-        let client = Client::new();
-        let res = client.head(url).send()?.error_for_status()?;
+        let res = scheduler.client().head(url).send()?.error_for_status()?;
 
         if res.headers().get("accept-ranges").is_none() {
             println!("⚠️ Server does not support segmented downloading.");
@@ -51,6 +78,20 @@ pub fn download_file_segmented(
 
         let etag = res.headers().get("etag").map(|v| v.to_str().unwrap_or("").to_string());
         let last_modified = res.headers().get("last-modified").map(|v| v.to_str().unwrap_or("").to_string());
+        // `Digest` values are already `algo=value` (e.g. `sha-256=...`), but a
+        // bare `Content-MD5` header is just the base64 digest with no algo
+        // prefix — tag it as `md5=` here so `verify_remote_digest` always sees
+        // a normalized `algo=value` string and never has to guess which
+        // header a value came from.
+        let remote_digest = res
+            .headers()
+            .get("digest")
+            .map(|v| v.to_str().unwrap_or("").to_string())
+            .or_else(|| {
+                res.headers()
+                    .get("content-md5")
+                    .map(|v| format!("md5={}", v.to_str().unwrap_or("")))
+            });
 
         let chunk_size = total_size / num_threads as u64;
         let mut segments = Vec::new();
@@ -72,16 +113,23 @@ pub fn download_file_segmented(
             });
         }
 
-        metadata = DownloadMetadata {
+        let fresh = DownloadMetadata {
             url: url.to_string(),
             output_path: output_path.to_string(),
             total_size,
             etag,
             last_modified,
             segments,
+            expected_checksum: expected_checksum.clone(),
+            remote_digest,
         };
 
-        metadata.save_to_file(&meta_path)?;
+        fresh.save_to_file(&meta_path)?;
+        fresh
+    };
+
+    if metadata.expected_checksum.is_none() {
+        metadata.expected_checksum = expected_checksum.clone();
     }
 
     println!("📦 Total size: {} bytes", metadata.total_size);
@@ -89,13 +137,25 @@ pub fn download_file_segmented(
 
     let pause_flag = external_pause_flag.unwrap_or_else(|| Arc::new(AtomicBool::new(false)));
     let status = external_status.unwrap_or_else(|| Arc::new(Mutex::new(DownloadStatus::Idle)));
-    let tracker = external_tracker.unwrap_or_else(|| {
-        Arc::new(Mutex::new(SegmentedProgressTracker::new(
+    let tracker = match external_tracker {
+        Some(tracker) => {
+            // Built by the caller before this function knew `total_size` —
+            // fill it in now so progress/ETA aren't stuck reporting against 0.
+            let mut guard = tracker.lock().unwrap();
+            guard.total_size = metadata.total_size;
+            for segment in &metadata.segments {
+                guard.register_block(segment.segment_id, segment.end - segment.start + 1);
+            }
+            drop(guard);
+            tracker
+        }
+        None => Arc::new(Mutex::new(SegmentedProgressTracker::new(
             metadata.segments.len(),
             metadata.total_size / metadata.segments.len() as u64,
             metadata.total_size,
-        )))
-    });
+        ))),
+    };
+    let sleep_tracker = external_sleep_tracker.unwrap_or_else(|| Arc::new(SleepTracker::new()));
 
     let pause_flag_for_signal = pause_flag.clone();
     ctrlc::set_handler(move || {
@@ -103,12 +163,17 @@ pub fn download_file_segmented(
         pause_flag_for_signal.store(true, Ordering::Relaxed);
     }).expect("Failed to set Ctrl+C handler");
 
-    let metadata_for_saving = metadata.clone();
+    let mut metadata_for_saving = metadata.clone();
     let meta_path_clone = meta_path.clone();
     let pause_flag_for_saving = pause_flag.clone();
     thread::spawn(move || {
         while !pause_flag_for_saving.load(Ordering::Relaxed) {
             thread::sleep(Duration::from_secs(5));
+
+            for segment in metadata_for_saving.segments.iter_mut() {
+                segment.downloaded = fs::metadata(&segment.part_path).map(|m| m.len()).unwrap_or(0);
+            }
+
             if let Err(e) = metadata_for_saving.save_to_file(&meta_path_clone) {
                 eprintln!("⚠️ Auto-save failed: {}", e);
             } else {
@@ -117,24 +182,319 @@ pub fn download_file_segmented(
         }
     });
 
-    let mut handles = vec![];
+    // A segment queued for an attempt, carrying how much of it is already on
+    // disk and which attempt number this will be.
+    struct SegmentWork {
+        segment: DownloadSegment,
+        downloaded: u64,
+        attempt: u8,
+    }
 
+    // Pool of `num_threads` generic workers pulling from a shared ready
+    // queue, instead of one thread permanently dedicated to one segment for
+    // its whole lifetime. A segment that needs to back off after a
+    // retriable failure is parked (via `SleepTracker::park`, non-blocking)
+    // rather than sleeping on the worker that happened to be running it —
+    // once its deadline elapses any free worker can pick it back up, so a
+    // backed-off segment never pins a thread that could otherwise be
+    // servicing other ready work.
+    let ready: Arc<Mutex<VecDeque<SegmentWork>>> = Arc::new(Mutex::new(VecDeque::new()));
     for segment_meta in metadata.segments.clone() {
+        let downloaded = fs::metadata(&segment_meta.part_path).map(|m| m.len()).unwrap_or(0);
+        let segment = DownloadSegment::new(
+            metadata.url.clone(),
+            segment_meta,
+            Arc::clone(&tracker),
+            config.clone(),
+            metadata.etag.clone(),
+            pause_flag.clone(),
+            Arc::clone(&sleep_tracker),
+            Arc::clone(&scheduler),
+        );
+        ready.lock().unwrap().push_back(SegmentWork { segment, downloaded, attempt: 0 });
+    }
+
+    let parked: Arc<Mutex<Vec<SegmentWork>>> = Arc::new(Mutex::new(Vec::new()));
+    let remaining = Arc::new(AtomicUsize::new(metadata.segments.len()));
+    // Set when any segment detects the remote resource changed mid-download
+    // (stale `If-Range` resume). Every segment's progress against the old
+    // resource becomes suspect at that point, not just the one that noticed
+    // — so this stops the whole dispatcher rather than just failing that
+    // segment, and the parts/metadata get discarded and restarted below.
+    let stale = Arc::new(AtomicBool::new(false));
+
+    let mut handles = vec![];
+    for _ in 0..num_threads.max(1) {
+        let ready = Arc::clone(&ready);
+        let parked = Arc::clone(&parked);
+        let sleep_tracker = Arc::clone(&sleep_tracker);
+        let remaining = Arc::clone(&remaining);
+        let pause_flag = pause_flag.clone();
+        let stale = Arc::clone(&stale);
+
+        handles.push(thread::spawn(move || {
+            while remaining.load(Ordering::SeqCst) > 0
+                && !pause_flag.load(Ordering::Relaxed)
+                && !stale.load(Ordering::Relaxed)
+            {
+                // Promote any parked segments whose backoff deadline elapsed.
+                {
+                    let mut parked_guard = parked.lock().unwrap();
+                    let mut i = 0;
+                    while i < parked_guard.len() {
+                        if sleep_tracker.due(parked_guard[i].segment.meta.segment_id) {
+                            ready.lock().unwrap().push_back(parked_guard.remove(i));
+                        } else {
+                            i += 1;
+                        }
+                    }
+                }
+
+                let mut work = match ready.lock().unwrap().pop_front() {
+                    Some(w) => w,
+                    None => {
+                        thread::sleep(Duration::from_millis(100));
+                        continue;
+                    }
+                };
+
+                work.attempt += 1;
+                match work.segment.try_once(work.downloaded, work.attempt) {
+                    AttemptOutcome::Done | AttemptOutcome::Paused => {
+                        remaining.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    AttemptOutcome::Permanent(reason) => {
+                        eprintln!("❌ Segment {} failed: {}", work.segment.meta.segment_id, reason);
+                        remaining.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    AttemptOutcome::StaleResume(reason) => {
+                        eprintln!("⚠️ Segment {} stale resume: {}", work.segment.meta.segment_id, reason);
+                        stale.store(true, Ordering::Relaxed);
+                        remaining.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    AttemptOutcome::Retry(downloaded) => {
+                        if work.attempt >= work.segment.config.max_retries {
+                            eprintln!(
+                                "❌ Segment {} failed after {} attempts",
+                                work.segment.meta.segment_id, work.segment.config.max_retries
+                            );
+                            remaining.fetch_sub(1, Ordering::SeqCst);
+                            continue;
+                        }
+
+                        work.downloaded = downloaded;
+                        let delay = work.segment.backoff_delay(work.attempt);
+                        println!(
+                            "💤 Segment {} backing off for {:.2}s before retry",
+                            work.segment.meta.segment_id,
+                            delay.as_secs_f64()
+                        );
+                        sleep_tracker.park(work.segment.meta.segment_id, delay);
+                        parked.lock().unwrap().push(work);
+                    }
+                }
+            }
+        }));
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    if stale.load(Ordering::Relaxed) {
+        println!("⚠️ Remote resource changed mid-download — discarding stale parts and restarting.");
+        discard_parts(&metadata);
+        fs::remove_file(&meta_path).ok();
+        // Reset in place (rather than handing the recursive call a fresh
+        // `Arc`) so a caller holding onto this tracker keeps seeing updates
+        // instead of a stale snapshot from the discarded attempt.
+        *tracker.lock().unwrap() = SegmentedProgressTracker::new(num_threads, 0, 0);
+        return download_file_segmented(
+            url,
+            output_path,
+            num_threads,
+            config,
+            Some(pause_flag),
+            Some(status),
+            Some(tracker),
+            Some(sleep_tracker),
+            Some(scheduler),
+            expected_checksum,
+        );
+    }
+
+    if pause_flag.load(Ordering::Relaxed) {
+        println!("💾 Saving metadata on pause...");
+        metadata.save_to_file(&meta_path)?;
+        println!("⏸️ Download paused.");
+        return Ok(());
+    }
+
+    // Merge into a `.partial` file first — it's only renamed to `output_path`
+    // once verified against the requested checksum or the server's digest
+    // (or immediately if there's neither), so a
+    // consumer never sees a half-written or unverified file at the final path.
+    let partial_path = format!("{}.partial", output_path);
+    merge_files(output_path, &partial_path, metadata.segments.len())?;
+
+    if let Some(checksum) = &metadata.expected_checksum {
+        print!("🔒 Verifying checksum...");
+        io::stdout().flush().ok();
+        if let Err(reason) = verify_checksum(&partial_path, checksum) {
+            eprintln!(
+                "\n❌ Integrity check failed: {} — keeping .part files and metadata for retry.",
+                reason
+            );
+            fs::remove_file(&partial_path).ok();
+            *status.lock().unwrap() = DownloadStatus::Failed(reason.clone());
+            return Err(reason.into());
+        }
+        println!(" ok");
+    } else if let Some(remote_digest) = &metadata.remote_digest {
+        // No explicit checksum was requested, but the server advertised a
+        // `Digest`/`Content-MD5` header at download time — verify against
+        // that for free rather than letting it sit unused.
+        print!("🔒 Verifying against remote digest...");
+        io::stdout().flush().ok();
+        match verify_remote_digest(&partial_path, remote_digest) {
+            Ok(()) => println!(" ok"),
+            Err(VerifyDigestError::Mismatch(reason)) => {
+                eprintln!(
+                    "\n❌ Integrity check failed: {} — keeping .part files and metadata for retry.",
+                    reason
+                );
+                fs::remove_file(&partial_path).ok();
+                *status.lock().unwrap() = DownloadStatus::Failed(reason.clone());
+                return Err(reason.into());
+            }
+            Err(VerifyDigestError::UnsupportedAlgorithm(algo)) => {
+                println!(" skipped (unsupported digest algorithm: {})", algo);
+            }
+        }
+    }
+
+    fs::rename(&partial_path, output_path)?;
+    for i in 0..metadata.segments.len() {
+        fs::remove_file(format!("{}.part{}", output_path, i)).ok();
+    }
+    fs::remove_file(&meta_path).ok();
+    println!("\n✅ All segments merged to: {}", output_path);
+
+    Ok(())
+}
+
+/// Like `download_file_segmented`, but instead of assembling a `.tar.gz` /
+/// `.tar.bz2` / `.tar.lz4` archive to disk, pipes downloaded bytes straight
+/// into a decode/unpack stage as they arrive so the archive never lands on
+/// disk as one intermediate file.
+pub fn download_file_segmented_extract(
+    url: &str,
+    target_dir: &str,
+    num_threads: usize,
+    config: &Config,
+    external_pause_flag: Option<Arc<AtomicBool>>,
+    external_tracker: Option<Arc<Mutex<SegmentedProgressTracker>>>,
+    external_scheduler: Option<Arc<Scheduler>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scheduler = external_scheduler.unwrap_or_else(|| {
+        Arc::new(Scheduler::new(
+            Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()
+                .expect("failed to build shared HTTP client"),
+            config.max_concurrent,
+        ))
+    });
+    let res = scheduler.client().head(url).send()?.error_for_status()?;
+
+    let content_type = res
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok());
+    let kind = match config.archive_kind_override.as_deref().and_then(ArchiveKind::from_override) {
+        Some(kind) => kind,
+        None => ArchiveKind::detect(url, content_type)
+            .ok_or_else(|| format!("unrecognized archive format for {}", url))?,
+    };
+
+    if res.headers().get("accept-ranges").is_none() {
+        return Err("server does not support segmented downloading, required for streaming extract".into());
+    }
+
+    let total_size = res
+        .headers()
+        .get("content-length")
+        .ok_or("no Content-Length header")?
+        .to_str()?
+        .parse::<u64>()?;
+
+    let chunk_size = total_size / num_threads as u64;
+    let mut segments = Vec::new();
+    for i in 0..num_threads {
+        let start = i as u64 * chunk_size;
+        let end = if i == num_threads - 1 {
+            total_size - 1
+        } else {
+            (i as u64 + 1) * chunk_size - 1
+        };
+        segments.push(SegmentMetadata {
+            segment_id: i,
+            start,
+            end,
+            downloaded: 0,
+            part_path: String::new(),
+        });
+    }
+
+    fs::create_dir_all(target_dir)?;
+
+    let tracker = match external_tracker {
+        Some(tracker) => {
+            // The caller built this tracker before the HEAD request above
+            // could run, so it doesn't know `total_size`/segment sizes yet —
+            // fill them in now rather than leaving it stuck at all-zero.
+            let mut guard = tracker.lock().unwrap();
+            guard.total_size = total_size;
+            for i in 0..num_threads {
+                guard.segments.insert(i, (0, chunk_size));
+            }
+            drop(guard);
+            tracker
+        }
+        None => Arc::new(Mutex::new(SegmentedProgressTracker::new(
+            num_threads,
+            chunk_size,
+            total_size,
+        ))),
+    };
+    let sleep_tracker = Arc::new(SleepTracker::new());
+    let pause_flag = external_pause_flag.unwrap_or_else(|| Arc::new(AtomicBool::new(false)));
+
+    let (chunk_sender, extractor_handle) =
+        extract::spawn_extractor(kind, 0, Path::new(target_dir), Arc::clone(&tracker), num_threads * 4);
+
+    let mut handles = vec![];
+    for segment_meta in segments {
         let tracker_clone = Arc::clone(&tracker);
         let config_clone = config.clone();
-        let url_clone = metadata.url.clone();
-        let etag = metadata.etag.clone();
-        let pause_flag_clone = pause_flag.clone();
+        let url_clone = url.to_string();
+        let pause_flag_clone = Arc::clone(&pause_flag);
+        let sleep_tracker_clone = Arc::clone(&sleep_tracker);
+        let scheduler_clone = Arc::clone(&scheduler);
+        let sink = chunk_sender.clone();
 
         let handle = thread::spawn(move || {
             let segment = DownloadSegment::new(
                 url_clone,
                 segment_meta,
                 tracker_clone,
-                &config_clone,
-                etag,
+                config_clone,
+                None,
                 pause_flag_clone,
-            );
+                sleep_tracker_clone,
+                scheduler_clone,
+            )
+            .with_extract_sink(sink);
 
             if let Err(e) = segment.download() {
                 eprintln!("❌ Segment {} failed: {}", segment.meta.segment_id, e);
@@ -143,41 +503,271 @@ pub fn download_file_segmented(
 
         handles.push(handle);
     }
+    drop(chunk_sender);
 
     for handle in handles {
         handle.join().unwrap();
     }
 
-    if pause_flag.load(Ordering::Relaxed) {
-        println!("💾 Saving metadata on pause...");
-        metadata.save_to_file(&meta_path)?;
-        println!("⏸️ Download paused.");
-        return Ok(());
+    extractor_handle
+        .join()
+        .map_err(|_| "extractor thread panicked")??;
+
+    println!("\n✅ Extracted to: {}", target_dir);
+    Ok(())
+}
+
+/// Checks a persisted `DownloadMetadata` against a fresh `HEAD` request so a
+/// resumed download never silently continues appending to parts of a
+/// resource that changed server-side since the metadata was written.
+fn validate_resume(
+    client: &Client,
+    url: &str,
+    candidate: &DownloadMetadata,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let res = client.head(url).send()?.error_for_status()?;
+
+    if res.headers().get("accept-ranges").is_none() {
+        // Ranged resume is no longer possible at all — treat as stale so the
+        // caller discards the `.partN` files and falls back to a fresh plan.
+        return Ok(false);
     }
 
-    merge_files(output_path, metadata.segments.len())?;
-    fs::remove_file(&meta_path).ok();
-    println!("\n✅ All segments merged to: {}", output_path);
+    let total_size = res
+        .headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok());
+    if let Some(size) = total_size {
+        if size != candidate.total_size {
+            return Ok(false);
+        }
+    }
 
-    Ok(())
+    let etag = res
+        .headers()
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    if candidate.etag.is_some() && candidate.etag != etag {
+        return Ok(false);
+    }
+
+    let last_modified = res
+        .headers()
+        .get("last-modified")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    if candidate.etag.is_none() && candidate.last_modified != last_modified {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Removes the `.partN` files belonging to a stale/invalidated metadata
+/// record so a fresh download starts from a clean slate.
+fn discard_parts(metadata: &DownloadMetadata) {
+    for segment in &metadata.segments {
+        let _ = fs::remove_file(&segment.part_path);
+    }
 }
 
-fn merge_files(output_path: &str, num_parts: usize) -> io::Result<()> {
-    let mut output = File::create(output_path)?;
+/// Concatenates the `.partN` files into `partial_path` (conventionally
+/// `{output_path}.partial`), leaving the parts in place — the caller deletes
+/// them only once the merged file is verified (or there's nothing to
+/// verify), so a checksum mismatch can still be retried from the parts.
+fn merge_files(output_path: &str, partial_path: &str, num_parts: usize) -> io::Result<()> {
+    let mut output = File::create(partial_path)?;
 
     for i in 0..num_parts {
         let part_path = format!("{}.part{}", output_path, i);
         let mut part_file = File::open(&part_path)?;
         io::copy(&mut part_file, &mut output)?;
-        fs::remove_file(&part_path)?;
     }
 
     Ok(())
 }
 
+/// Streams `path` through the matching hasher and compares the result
+/// against `expected`, without ever loading the whole file into memory.
+fn verify_checksum(path: &str, expected: &Checksum) -> Result<(), String> {
+    let (algo, want, got) = match expected {
+        Checksum::Sha256(want) => ("sha256", want, compute_sha256(path)),
+        Checksum::Sha1(want) => ("sha1", want, compute_sha1(path)),
+        Checksum::Md5(want) => ("md5", want, compute_md5(path)),
+    };
+    let got = got.map_err(|e| format!("failed to hash {}: {}", path, e))?;
+
+    if got.eq_ignore_ascii_case(want) {
+        Ok(())
+    } else {
+        Err(format!("checksum mismatch: expected {}:{}, got {}:{}", algo, want, algo, got))
+    }
+}
+
+/// Why `verify_remote_digest` didn't return `Ok`.
+enum VerifyDigestError {
+    /// The computed digest didn't match, or the file couldn't be hashed.
+    Mismatch(String),
+    /// The header named an algorithm we don't know how to hash against;
+    /// this isn't a corruption signal, just an unverifiable header.
+    UnsupportedAlgorithm(String),
+}
+
+/// Verifies `path` against a `Digest`/`Content-MD5`-style header value
+/// captured at download time, in `algo=<base64>` form (e.g.
+/// `sha-256=<base64>`, or `md5=<base64>` for a tagged `Content-MD5`). A
+/// value with no `=` at all is hashed as MD5; an untagged base64 value
+/// with `=` padding is split at the padding and reported as an
+/// unsupported algorithm.
+fn verify_remote_digest(path: &str, remote_digest: &str) -> Result<(), VerifyDigestError> {
+    let (algo, want_b64) = match remote_digest.split_once('=') {
+        Some((algo, value)) if matches!(algo.to_ascii_lowercase().as_str(), "sha-256" | "sha-1" | "md5") => {
+            (algo.to_ascii_lowercase(), value.to_string())
+        }
+        Some((algo, _)) => return Err(VerifyDigestError::UnsupportedAlgorithm(algo.to_string())),
+        None => ("md5".to_string(), remote_digest.to_string()),
+    };
+
+    let got = match algo.as_str() {
+        "sha-256" => compute_sha256_b64(path),
+        "sha-1" => compute_sha1_b64(path),
+        _ => compute_md5_b64(path),
+    }
+    .map_err(|e| VerifyDigestError::Mismatch(format!("failed to hash {}: {}", path, e)))?;
+
+    if got == want_b64 {
+        Ok(())
+    } else {
+        Err(VerifyDigestError::Mismatch(format!(
+            "remote digest mismatch: expected {}={}, got {}={}",
+            algo, want_b64, algo, got
+        )))
+    }
+}
+
+fn compute_sha256_b64(path: &str) -> io::Result<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use sha2::{Digest, Sha256};
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(STANDARD.encode(hasher.finalize()))
+}
+
+fn compute_sha1_b64(path: &str) -> io::Result<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use sha1::{Digest, Sha1};
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(STANDARD.encode(hasher.finalize()))
+}
+
+fn compute_md5_b64(path: &str) -> io::Result<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buffer[..n]);
+    }
+    Ok(STANDARD.encode(context.compute().0))
+}
+
+fn compute_sha256(path: &str) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn compute_sha1(path: &str) -> io::Result<String> {
+    use sha1::{Digest, Sha1};
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn compute_md5(path: &str) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buffer[..n]);
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
 pub fn hash_url(url: &str) -> String {
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
     hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp_file(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("odm_verify_checksum_{}", name));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn verify_checksum_accepts_a_matching_sha256() {
+        let path = write_temp_file("sha256_match", b"hello world");
+        let expected = Checksum::Sha256(compute_sha256(&path).unwrap());
+        assert!(verify_checksum(&path, &expected).is_ok());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_checksum_rejects_a_mismatch() {
+        let path = write_temp_file("sha256_mismatch", b"hello world");
+        let expected = Checksum::Sha256("0".repeat(64));
+        assert!(verify_checksum(&path, &expected).is_err());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_checksum_is_case_insensitive() {
+        let path = write_temp_file("md5_case", b"hello world");
+        let want = compute_md5(&path).unwrap().to_ascii_uppercase();
+        assert!(verify_checksum(&path, &Checksum::Md5(want)).is_ok());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_remote_digest_accepts_a_tagged_content_md5() {
+        let path = write_temp_file("remote_digest_md5", b"hello world");
+        let want = compute_md5_b64(&path).unwrap();
+        // Mirrors how a bare Content-MD5 header gets tagged at capture time.
+        let tagged = format!("md5={}", want);
+        assert!(verify_remote_digest(&path, &tagged).is_ok());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn verify_remote_digest_rejects_an_unsupported_algorithm() {
+        let path = write_temp_file("remote_digest_unsupported", b"hello world");
+        assert!(matches!(
+            verify_remote_digest(&path, "sha-512=does-not-matter"),
+            Err(VerifyDigestError::UnsupportedAlgorithm(_))
+        ));
+        fs::remove_file(&path).ok();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,7 @@
+pub mod extract;
+pub mod file;
+pub mod manager;
+pub mod progress;
+pub mod segment;
+pub mod single;
+pub mod workpool;
@@ -1,10 +1,67 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+
+/// How far back `SpeedWindow` looks when estimating bytes/sec.
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Rolling bytes/sec estimate over a short trailing window of
+/// `(Instant, total_downloaded)` samples, used to derive both current
+/// throughput and ETA without re-deriving them at every call site.
+struct SpeedWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedWindow {
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, total: u64) {
+        let now = Instant::now();
+        self.samples.push_back((now, total));
+        while let Some(&(oldest, _)) = self.samples.front() {
+            if now.duration_since(oldest) > SPEED_WINDOW {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Bytes/sec over the window, or `None` if there isn't enough history yet.
+    fn bytes_per_sec(&self) -> Option<f64> {
+        let (oldest_t, oldest_total) = *self.samples.front()?;
+        let (newest_t, newest_total) = *self.samples.back()?;
+        let elapsed = newest_t.duration_since(oldest_t).as_secs_f64();
+        if elapsed <= 0.0 || newest_total <= oldest_total {
+            return None;
+        }
+        Some((newest_total - oldest_total) as f64 / elapsed)
+    }
+}
+
+fn format_eta(seconds: f64) -> String {
+    let total = seconds.round().max(0.0) as u64;
+    let hours = total / 3600;
+    let minutes = (total % 3600) / 60;
+    let secs = total % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
 
 /// Used by the single-threaded downloader
 pub struct SimpleProgressTracker {
     total_size: u64,
     downloaded: u64,
+    speed: SpeedWindow,
 }
 
 impl SimpleProgressTracker {
@@ -12,13 +69,55 @@ impl SimpleProgressTracker {
         Self {
             total_size,
             downloaded: 0,
+            speed: SpeedWindow::new(),
         }
     }
 
     pub fn update(&mut self, bytes: u64) {
         self.downloaded += bytes;
-        let percent = (self.downloaded as f64 / self.total_size as f64) * 100.0;
-        print!("\r⏬ Downloading: {:.2}%", percent);
+        self.speed.record(self.downloaded);
+        self.display();
+    }
+
+    pub fn speed_bps(&self) -> Option<f64> {
+        self.speed.bytes_per_sec()
+    }
+
+    pub fn eta_secs(&self) -> Option<f64> {
+        let bps = self.speed_bps()?;
+        if self.total_size == 0 || bps <= 0.0 {
+            return None;
+        }
+        Some((self.total_size.saturating_sub(self.downloaded) as f64 / bps).max(0.0))
+    }
+
+    fn display(&self) {
+        let downloaded = ByteSize(self.downloaded);
+
+        match (self.total_size > 0, self.speed_bps()) {
+            (true, Some(bps)) => {
+                let percent = (self.downloaded as f64 / self.total_size as f64) * 100.0;
+                let eta = self.eta_secs().map(format_eta).unwrap_or_else(|| "?".to_string());
+                print!(
+                    "\r⏬ {} / {} — {:.2}% — {}/s — ETA {}",
+                    downloaded,
+                    ByteSize(self.total_size),
+                    percent,
+                    ByteSize(bps as u64),
+                    eta
+                );
+            }
+            (true, None) => {
+                let percent = (self.downloaded as f64 / self.total_size as f64) * 100.0;
+                print!("\r⏬ Downloading: {:.2}%", percent);
+            }
+            (false, Some(bps)) => {
+                print!("\r⏬ {} — {}/s", downloaded, ByteSize(bps as u64));
+            }
+            (false, None) => {
+                print!("\r⏬ {}", downloaded);
+            }
+        }
         io::stdout().flush().unwrap();
     }
 }
@@ -28,6 +127,12 @@ pub struct SegmentedProgressTracker {
     pub segments: HashMap<usize, (u64, u64)>, // segment_id: (downloaded, total)
     pub total_downloaded: u64,
     pub total_size: u64,
+    pub extracted_bytes: u64,
+    pub extracting: bool,
+    /// segment_id: (attempt, max_retries) for segments currently backed off
+    /// after a retriable failure; absent once a segment is streaming again.
+    pub retrying: HashMap<usize, (u8, u8)>,
+    speed: SpeedWindow,
 }
 
 impl SegmentedProgressTracker {
@@ -40,6 +145,10 @@ impl SegmentedProgressTracker {
             segments,
             total_downloaded: 0,
             total_size,
+            extracted_bytes: 0,
+            extracting: false,
+            retrying: HashMap::new(),
+            speed: SpeedWindow::new(),
         }
     }
 
@@ -48,12 +157,80 @@ impl SegmentedProgressTracker {
             *downloaded += bytes;
         }
         self.total_downloaded += bytes;
+        self.speed.record(self.total_downloaded);
+        self.retrying.remove(&segment_id);
         self.display();
     }
 
+    /// Marks `segment_id` as backed off before retry `attempt` of
+    /// `max_retries`, so callers can render "retrying (3/5)".
+    pub fn set_retrying(&mut self, segment_id: usize, attempt: u8, max_retries: u8) {
+        self.retrying.insert(segment_id, (attempt, max_retries));
+    }
+
+    pub fn clear_retrying(&mut self, segment_id: usize) {
+        self.retrying.remove(&segment_id);
+    }
+
+    /// Adds (or resets) the tracked entry for a segment or block whose size
+    /// wasn't known at construction time.
+    pub fn register_block(&mut self, block_id: usize, size: u64) {
+        self.segments.insert(block_id, (0, size));
+    }
+
+    /// Records bytes that have been decompressed/unpacked by the extraction
+    /// stage, kept separate from download progress since the two diverge.
+    pub fn update_extracted(&mut self, bytes: u64) {
+        self.extracted_bytes += bytes;
+    }
+
+    pub fn set_extracting(&mut self, extracting: bool) {
+        self.extracting = extracting;
+    }
+
+    /// Bytes/sec over the last `SPEED_WINDOW`, or `None` if there isn't
+    /// enough history yet.
+    pub fn speed_bps(&self) -> Option<f64> {
+        self.speed.bytes_per_sec()
+    }
+
+    /// Estimated seconds remaining at the current rate. `None` when the
+    /// total size is unknown (no Content-Length) or there's no rate yet.
+    pub fn eta_secs(&self) -> Option<f64> {
+        let bps = self.speed_bps()?;
+        if self.total_size == 0 || bps <= 0.0 {
+            return None;
+        }
+        Some((self.total_size.saturating_sub(self.total_downloaded) as f64 / bps).max(0.0))
+    }
+
     pub fn display(&self) {
-        let percent = (self.total_downloaded as f64 / self.total_size as f64) * 100.0;
-        print!("\r⏬ Overall Progress: {:.2}%", percent);
+        let downloaded = ByteSize(self.total_downloaded);
+
+        match (self.total_size > 0, self.speed_bps()) {
+            (true, Some(bps)) => {
+                let percent = (self.total_downloaded as f64 / self.total_size as f64) * 100.0;
+                let eta = self.eta_secs().map(format_eta).unwrap_or_else(|| "?".to_string());
+                print!(
+                    "\r⏬ {} / {} — {:.2}% — {}/s — ETA {}",
+                    downloaded,
+                    ByteSize(self.total_size),
+                    percent,
+                    ByteSize(bps as u64),
+                    eta
+                );
+            }
+            (true, None) => {
+                let percent = (self.total_downloaded as f64 / self.total_size as f64) * 100.0;
+                print!("\r⏬ Overall Progress: {:.2}%", percent);
+            }
+            (false, Some(bps)) => {
+                print!("\r⏬ {} — {}/s", downloaded, ByteSize(bps as u64));
+            }
+            (false, None) => {
+                print!("\r⏬ {}", downloaded);
+            }
+        }
         io::stdout().flush().unwrap();
     }
 }
@@ -1,4 +1,4 @@
-use reqwest::blocking::Client;
+use rand::Rng;
 use reqwest::header::{RANGE, USER_AGENT, IF_RANGE};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
@@ -7,26 +7,43 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use crate::config::Config;
+use crate::core::manager::{Scheduler, SleepTracker};
+use crate::download::extract::{Chunk, ChunkSender};
 use crate::download::progress::SegmentedProgressTracker;
 use crate::state::metadata::SegmentMetadata;
 
-pub struct DownloadSegment<'a> {
+/// Whether a failed attempt is worth retrying.
+pub(crate) enum Failure {
+    Retriable,
+    Permanent(String),
+}
+
+#[derive(Clone)]
+pub struct DownloadSegment {
     pub url: String,
     pub meta: SegmentMetadata,
     pub tracker: Arc<Mutex<SegmentedProgressTracker>>,
-    pub config: &'a Config,
+    pub config: Config,
     pub etag: Option<String>,
     pub pause_flag: Arc<AtomicBool>,
+    pub sleep_tracker: Arc<SleepTracker>,
+    pub scheduler: Arc<Scheduler>,
+    /// When set, downloaded bytes are forwarded here in stream order for
+    /// on-the-fly decompression instead of being written to a `.partN`
+    /// file on disk.
+    pub extract_sink: Option<ChunkSender>,
 }
 
-impl<'a> DownloadSegment<'a> {
+impl DownloadSegment {
     pub fn new(
         url: String,
         meta: SegmentMetadata,
         tracker: Arc<Mutex<SegmentedProgressTracker>>,
-        config: &'a Config,
+        config: Config,
         etag: Option<String>,
         pause_flag: Arc<AtomicBool>,
+        sleep_tracker: Arc<SleepTracker>,
+        scheduler: Arc<Scheduler>,
     ) -> Self {
         Self {
             url,
@@ -35,95 +52,257 @@ impl<'a> DownloadSegment<'a> {
             config,
             etag,
             pause_flag,
+            sleep_tracker,
+            scheduler,
+            extract_sink: None,
         }
     }
 
+    pub fn with_extract_sink(mut self, sink: ChunkSender) -> Self {
+        self.extract_sink = Some(sink);
+        self
+    }
+
     pub fn download(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let downloaded = get_downloaded_size(&self.meta.part_path)?;
-        if downloaded >= (self.meta.end - self.meta.start + 1) {
+        let segment_size = self.meta.end - self.meta.start + 1;
+        let mut downloaded = get_downloaded_size(&self.meta.part_path)?;
+        if downloaded >= segment_size {
             println!("✔️ Segment {} already done.", self.meta.segment_id);
             return Ok(());
         }
 
+        for attempt in 1..=self.config.max_retries {
+            if downloaded >= segment_size {
+                return Ok(());
+            }
+
+            match self.try_once(downloaded, attempt) {
+                AttemptOutcome::Done | AttemptOutcome::Paused => return Ok(()),
+                AttemptOutcome::Retry(d) => {
+                    downloaded = d;
+                    let delay = self.backoff_delay(attempt);
+                    println!(
+                        "💤 Segment {} backing off for {:.2}s before retry",
+                        self.meta.segment_id,
+                        delay.as_secs_f64()
+                    );
+                    self.sleep_tracker.sleep_until(self.meta.segment_id, delay);
+                }
+                AttemptOutcome::Permanent(reason) | AttemptOutcome::StaleResume(reason) => {
+                    return Err(reason.into())
+                }
+            }
+        }
+
+        Err(format!(
+            "Segment {} failed after {} attempts",
+            self.meta.segment_id, self.config.max_retries
+        )
+        .into())
+    }
+
+    /// Runs exactly one attempt, resuming from `downloaded` bytes already
+    /// written. Never sleeps — a caller driving a shared pool of segments
+    /// (see `download_file_segmented`'s dispatcher) needs the backoff delay
+    /// kept out-of-band so a backed-off segment doesn't pin a worker thread
+    /// that could otherwise service a different segment in the meantime.
+    pub(crate) fn try_once(&self, downloaded: u64, attempt: u8) -> AttemptOutcome {
+        println!(
+            "📡 Segment {} downloading (attempt {}/{})...",
+            self.meta.segment_id, attempt, self.config.max_retries
+        );
+
+        // Recomputed from `downloaded` every attempt: a failed attempt may
+        // have written a partial chunk before erroring, so a retry must
+        // resume from `self.meta.start + downloaded`, not re-request the
+        // whole segment and duplicate the bytes already on disk/in the sink.
         let start = self.meta.start + downloaded;
         let range_header = format!("bytes={}-{}", start, self.meta.end);
 
-        for attempt in 1..=self.config.max_retries {
-            println!(
-                "📡 Segment {} downloading (attempt {}/{})...",
-                self.meta.segment_id, attempt, self.config.max_retries
-            );
-
-            let client = Client::builder()
-                .timeout(Duration::from_secs(self.config.timeout_secs))
-                .build()?;
-
-            let mut request = client
-                .get(&self.url)
-                .header(RANGE, &range_header)
-                .header(USER_AGENT, "OpenDownloadManager/0.1");
-
-            if let Some(etag) = &self.etag {
-                request = request.header(IF_RANGE, etag);
-            }
+        // Block until the manager-wide concurrency cap has a free slot;
+        // held only for the duration of this attempt's request/response,
+        // released (by going out of scope) before the caller backs off.
+        let _token = self.scheduler.acquire();
+
+        let mut request = self
+            .scheduler
+            .client()
+            .get(&self.url)
+            .header(RANGE, &range_header)
+            .header(USER_AGENT, "OpenDownloadManager/0.1");
+
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_RANGE, etag);
+        }
 
-            let mut response = match request.send() {
-                Ok(res) if res.status().is_success() || res.status().as_u16() == 206 => res,
-                Ok(res) => {
+        let mut response = match request.send() {
+            Ok(res) if self.etag.is_some() && res.status().as_u16() == 200 => {
+                // We sent `If-Range`, so a `200` (instead of `206`) means
+                // the validator no longer matches and the server sent the
+                // full body back — writing it at this segment's byte
+                // offset would corrupt the part file. Report this distinctly
+                // from a generic permanent failure so the caller can discard
+                // every segment's stale resume state and restart cleanly,
+                // rather than just failing this one segment.
+                let reason = format!(
+                    "Segment {} got 200 instead of 206 for an If-Range request — remote resource changed, resume is stale",
+                    self.meta.segment_id
+                );
+                eprintln!("❌ {}", reason);
+                return AttemptOutcome::StaleResume(reason);
+            }
+            Ok(res) if res.status().is_success() || res.status().as_u16() == 206 => res,
+            Ok(res) => match classify_status(res.status().as_u16()) {
+                Failure::Permanent(reason) => {
+                    eprintln!("❌ Segment {} permanent error: {}", self.meta.segment_id, reason);
+                    return AttemptOutcome::Permanent(reason);
+                }
+                Failure::Retriable => {
                     eprintln!(
                         "❌ Segment {} HTTP error: {}",
                         self.meta.segment_id,
                         res.status()
                     );
-                    continue;
+                    return AttemptOutcome::Retry(downloaded);
                 }
-                Err(e) => {
-                    eprintln!("❌ Segment {} network error: {}", self.meta.segment_id, e);
-                    continue;
-                }
-            };
+            },
+            Err(e) => {
+                eprintln!("❌ Segment {} network error: {}", self.meta.segment_id, e);
+                return AttemptOutcome::Retry(downloaded);
+            }
+        };
+
+        let mut offset = start;
+        let mut paused = false;
 
-            let result = (|| -> Result<(), Box<dyn std::error::Error>> {
-                let mut file = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(&self.meta.part_path)?;
+        let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+            let mut file = match &self.extract_sink {
+                Some(_) => None,
+                None => Some(
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&self.meta.part_path)?,
+                ),
+            };
 
-                let mut buffer = [0; 8192];
+            let mut buffer = [0; 8192];
 
-                loop {
-                    if self.pause_flag.load(Ordering::Relaxed) {
-                        println!("⏸️ Segment {} paused", self.meta.segment_id);
-                        return Ok(());
-                    }
+            loop {
+                if self.pause_flag.load(Ordering::Relaxed) {
+                    println!("⏸️ Segment {} paused", self.meta.segment_id);
+                    paused = true;
+                    return Ok(());
+                }
 
-                    let n = response.read(&mut buffer)?;
-                    if n == 0 {
-                        break;
-                    }
+                let n = response.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
 
+                if let Some(file) = file.as_mut() {
                     file.write_all(&buffer[..n])?;
+                }
 
-                    let mut tracker = self.tracker.lock().unwrap();
-                    tracker.update(self.meta.segment_id, n as u64);
+                if let Some(sink) = &self.extract_sink {
+                    sink.send(Chunk {
+                        offset,
+                        bytes: buffer[..n].to_vec(),
+                    })
+                    .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "extractor closed"))?;
                 }
+                offset += n as u64;
 
-                Ok(())
-            })();
+                let mut tracker = self.tracker.lock().unwrap();
+                tracker.update(self.meta.segment_id, n as u64);
+            }
 
-            if result.is_ok() {
-                return Ok(());
+            Ok(())
+        })();
+
+        // Whatever made it into the file/sink before an error (or pause)
+        // counts toward `downloaded`, so the next attempt's Range header
+        // resumes after it instead of re-requesting bytes already written.
+        let downloaded = offset - self.meta.start;
+
+        match result {
+            Ok(()) if paused => AttemptOutcome::Paused,
+            Ok(()) => AttemptOutcome::Done,
+            Err(e) => {
+                eprintln!("❌ Segment {} stream error: {}", self.meta.segment_id, e);
+                AttemptOutcome::Retry(downloaded)
             }
         }
+    }
 
-        Err(format!(
-            "Segment {} failed after {} attempts",
-            self.meta.segment_id, self.config.max_retries
-        )
-        .into())
+    /// Marks the segment as backing off and computes the exponential delay
+    /// (with +/-50% jitter) before the next attempt, without sleeping.
+    pub(crate) fn backoff_delay(&self, attempt: u8) -> Duration {
+        self.tracker
+            .lock()
+            .unwrap()
+            .set_retrying(self.meta.segment_id, attempt, self.config.max_retries);
+
+        let base = self.config.retry_base_ms;
+        let cap = Duration::from_secs(self.config.retry_cap_secs);
+        let exp = base.saturating_mul(1u64 << attempt.saturating_sub(1).min(32));
+        let delay = Duration::from_millis(exp).min(cap);
+
+        let jitter = rand::thread_rng().gen_range(0.5..1.5);
+        delay.mul_f64(jitter)
+    }
+}
+
+/// Outcome of a single `DownloadSegment::try_once` attempt.
+pub(crate) enum AttemptOutcome {
+    /// The segment finished.
+    Done,
+    /// The pause flag was observed mid-stream; treated like `Done` by
+    /// callers (no more attempts), since the caller is responsible for
+    /// persisting progress and deciding whether to resume later.
+    Paused,
+    /// Worth another attempt, resuming from the given `downloaded` offset.
+    Retry(u64),
+    /// Not worth retrying — the segment (and its download) has failed.
+    Permanent(String),
+    /// An `If-Range` request got a full `200` response instead of `206`: the
+    /// remote resource changed since this segment's resume state was
+    /// written, so every segment's progress against the old resource is
+    /// suspect, not just this one. A caller driving several segments at
+    /// once (see `download_file_segmented`'s dispatcher) should discard all
+    /// of them and restart, rather than just failing this segment.
+    StaleResume(String),
+}
+
+/// Classifies a non-2xx/206 HTTP status as worth retrying or a permanent
+/// failure that shouldn't consume further retry attempts.
+pub(crate) fn classify_status(status: u16) -> Failure {
+    match status {
+        408 | 429 => Failure::Retriable,
+        500..=599 => Failure::Retriable,
+        _ => Failure::Permanent(format!("HTTP {}", status)),
     }
 }
 
 fn get_downloaded_size(path: &str) -> io::Result<u64> {
     std::fs::metadata(path).map(|m| m.len()).or(Ok(0))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_server_errors_and_rate_limiting_as_retriable() {
+        for status in [408, 429, 500, 503, 599] {
+            assert!(matches!(classify_status(status), Failure::Retriable));
+        }
+    }
+
+    #[test]
+    fn classifies_client_errors_as_permanent() {
+        for status in [400, 401, 403, 404, 410] {
+            assert!(matches!(classify_status(status), Failure::Permanent(_)));
+        }
+    }
+}
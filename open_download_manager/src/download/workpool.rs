@@ -0,0 +1,469 @@
+use rand::Rng;
+use reqwest::blocking::Client;
+use reqwest::header::{IF_RANGE, RANGE, USER_AGENT};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::core::manager::{Scheduler, SleepTracker};
+use crate::download::manager::hash_url;
+use crate::download::progress::SegmentedProgressTracker;
+use crate::download::segment::{classify_status, Failure};
+use crate::state::metadata::{BlockMetadata, WorkStealingMetadata};
+
+/// Below this many remaining bytes, a block isn't worth splitting further —
+/// the request overhead of a steal would outweigh the benefit.
+const MIN_STEAL_BYTES: u64 = 256 * 1024;
+
+/// One claimable unit of work. `cap` is the inclusive byte offset this block
+/// must stop at; it only ever shrinks, when another worker steals the back
+/// half of this block's remaining range.
+struct Block {
+    id: usize,
+    start: u64,
+    cap: AtomicU64,
+    downloaded: AtomicU64,
+    claimed_for_steal: AtomicBool,
+}
+
+/// Shared pool of blocks: a pending queue for never-started work plus the
+/// in-flight list a worker with nothing left to pop can scan to steal from.
+struct WorkPool {
+    pending: Mutex<VecDeque<Arc<Block>>>,
+    in_flight: Mutex<Vec<Arc<Block>>>,
+    next_id: AtomicUsize,
+}
+
+impl WorkPool {
+    fn new(blocks: Vec<Arc<Block>>, next_id: usize) -> Self {
+        Self {
+            pending: Mutex::new(blocks.into_iter().collect()),
+            in_flight: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(next_id),
+        }
+    }
+
+    /// Pops the next pending block, or — if the queue is empty — steals the
+    /// unfinished tail of the in-flight block with the most work remaining.
+    fn next_block(&self) -> Option<Arc<Block>> {
+        if let Some(block) = self.pending.lock().unwrap().pop_front() {
+            self.in_flight.lock().unwrap().push(Arc::clone(&block));
+            return Some(block);
+        }
+        self.try_steal()
+    }
+
+    fn try_steal(&self) -> Option<Arc<Block>> {
+        let candidate = {
+            let in_flight = self.in_flight.lock().unwrap();
+            in_flight
+                .iter()
+                .filter(|b| !b.claimed_for_steal.load(Ordering::SeqCst))
+                .filter_map(|b| {
+                    let cap = b.cap.load(Ordering::SeqCst);
+                    let downloaded = b.downloaded.load(Ordering::SeqCst);
+                    let remaining = (cap + 1).saturating_sub(b.start + downloaded);
+                    if remaining > MIN_STEAL_BYTES {
+                        Some((Arc::clone(b), remaining))
+                    } else {
+                        None
+                    }
+                })
+                .max_by_key(|(_, remaining)| *remaining)
+        };
+        let (victim, remaining) = candidate?;
+
+        if victim
+            .claimed_for_steal
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // Another stealer grabbed it between the scan and here; the
+            // caller will just loop and try again next time around.
+            return None;
+        }
+
+        let downloaded = victim.downloaded.load(Ordering::SeqCst);
+        let old_cap = victim.cap.load(Ordering::SeqCst);
+        let split_at = victim.start + downloaded + remaining / 2;
+        victim.cap.store(split_at - 1, Ordering::SeqCst);
+        victim.claimed_for_steal.store(false, Ordering::SeqCst);
+
+        let stolen = Arc::new(Block {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            start: split_at,
+            cap: AtomicU64::new(old_cap),
+            downloaded: AtomicU64::new(0),
+            claimed_for_steal: AtomicBool::new(false),
+        });
+        self.in_flight.lock().unwrap().push(Arc::clone(&stolen));
+        Some(stolen)
+    }
+
+    /// Snapshots every block this pool knows about (still pending or already
+    /// claimed) back into persistable form, so an autosave can capture
+    /// in-progress completion without waiting for the whole download to
+    /// finish.
+    fn snapshot_blocks(&self) -> Vec<BlockMetadata> {
+        let pending = self.pending.lock().unwrap();
+        let in_flight = self.in_flight.lock().unwrap();
+        pending
+            .iter()
+            .chain(in_flight.iter())
+            .map(|b| BlockMetadata {
+                block_id: b.id,
+                start: b.start,
+                end: b.cap.load(Ordering::SeqCst),
+                downloaded: b.downloaded.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+}
+
+/// Downloads a file by handing out small, independently-retried blocks from
+/// a shared work pool instead of splitting it into `num_workers` fixed
+/// contiguous ranges up front. A worker that runs out of pending blocks
+/// steals the unfinished tail of whichever in-flight block has the most
+/// work left, so one slow connection can't leave the rest idle. Writes go
+/// straight into `output_path` at each block's absolute offset — there's no
+/// merge step, since blocks don't correspond 1:1 with workers.
+pub fn download_file_work_stealing(
+    url: &str,
+    output_path: &str,
+    num_workers: usize,
+    config: &Config,
+    external_pause_flag: Option<Arc<AtomicBool>>,
+    external_tracker: Option<Arc<Mutex<SegmentedProgressTracker>>>,
+    external_scheduler: Option<Arc<Scheduler>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url_hash = hash_url(url);
+    let meta_path = format!("downloads/meta/{}.ws.meta.json", url_hash);
+    fs::create_dir_all("downloads/meta")?;
+
+    let scheduler = external_scheduler.unwrap_or_else(|| {
+        Arc::new(Scheduler::new(
+            Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()
+                .expect("failed to build shared HTTP client"),
+            config.max_concurrent,
+        ))
+    });
+
+    let res = scheduler.client().head(url).send()?.error_for_status()?;
+    if res.headers().get("accept-ranges").is_none() {
+        return Err("server does not support ranged requests, required for work-stealing mode".into());
+    }
+    let total_size = res
+        .headers()
+        .get("content-length")
+        .ok_or("no Content-Length header")?
+        .to_str()?
+        .parse::<u64>()?;
+    let etag = res.headers().get("etag").map(|v| v.to_str().unwrap_or("").to_string());
+    let last_modified = res.headers().get("last-modified").map(|v| v.to_str().unwrap_or("").to_string());
+
+    let resumable = WorkStealingMetadata::exists(&meta_path)
+        .then(|| WorkStealingMetadata::load_from_file(&meta_path).ok())
+        .flatten()
+        .filter(|candidate| {
+            candidate.total_size == total_size
+                && candidate.etag == etag
+                && (candidate.etag.is_some() || candidate.last_modified == last_modified)
+        });
+
+    let mut metadata = match resumable {
+        Some(candidate) => {
+            println!("🔄 Resuming work-stealing download from metadata...");
+            candidate
+        }
+        None => {
+            println!("⚠️ No usable resume state — starting a fresh block plan.");
+            let block_size = config.block_size_bytes.max(1);
+            let mut blocks = Vec::new();
+            let mut start = 0u64;
+            let mut id = 0usize;
+            while start < total_size {
+                let end = (start + block_size - 1).min(total_size - 1);
+                blocks.push(BlockMetadata { block_id: id, start, end, downloaded: 0 });
+                start = end + 1;
+                id += 1;
+            }
+
+            let file = File::create(output_path)?;
+            file.set_len(total_size)?;
+
+            let fresh = WorkStealingMetadata {
+                url: url.to_string(),
+                output_path: output_path.to_string(),
+                total_size,
+                etag,
+                last_modified,
+                blocks,
+            };
+            fresh.save_to_file(&meta_path)?;
+            fresh
+        }
+    };
+
+    println!("📦 Total size: {} bytes across {} blocks", metadata.total_size, metadata.blocks.len());
+    println!("🧵 Workers: {}", num_workers);
+
+    let next_id = metadata.blocks.iter().map(|b| b.block_id).max().map_or(0, |m| m + 1);
+    let tracker = match external_tracker {
+        Some(tracker) => {
+            // Built by the caller before this function knew `total_size` —
+            // fill it in now so progress/ETA aren't stuck reporting against 0.
+            tracker.lock().unwrap().total_size = metadata.total_size;
+            tracker
+        }
+        None => Arc::new(Mutex::new(SegmentedProgressTracker::new(0, 0, metadata.total_size))),
+    };
+
+    let blocks: Vec<Arc<Block>> = metadata
+        .blocks
+        .iter()
+        .filter(|b| b.downloaded < (b.end - b.start + 1))
+        .map(|b| {
+            tracker.lock().unwrap().register_block(b.block_id, b.end - b.start + 1);
+            Arc::new(Block {
+                id: b.block_id,
+                start: b.start + b.downloaded,
+                cap: AtomicU64::new(b.end),
+                downloaded: AtomicU64::new(0),
+                claimed_for_steal: AtomicBool::new(false),
+            })
+        })
+        .collect();
+
+    let pool = Arc::new(WorkPool::new(blocks, next_id));
+    let sleep_tracker = Arc::new(SleepTracker::new());
+    let pause_flag = external_pause_flag.unwrap_or_else(|| Arc::new(AtomicBool::new(false)));
+    let url = url.to_string();
+    let metadata_etag = metadata.etag.clone();
+
+    // Periodically flush block-level progress to disk so a killed process
+    // can resume from roughly where it left off instead of redownloading
+    // the whole file — mirrors `download_file_segmented`'s autosave thread.
+    let pool_for_saving = Arc::clone(&pool);
+    let meta_path_clone = meta_path.clone();
+    let pause_flag_for_saving = Arc::clone(&pause_flag);
+    let metadata_for_saving = metadata.clone();
+    thread::spawn(move || {
+        while !pause_flag_for_saving.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_secs(5));
+
+            let mut snapshot = metadata_for_saving.clone();
+            snapshot.blocks = pool_for_saving.snapshot_blocks();
+
+            if let Err(e) = snapshot.save_to_file(&meta_path_clone) {
+                eprintln!("⚠️ Auto-save failed: {}", e);
+            } else {
+                println!("💾 Auto-saved metadata");
+            }
+        }
+    });
+
+    let mut handles = Vec::new();
+    for _ in 0..num_workers.max(1) {
+        let pool = Arc::clone(&pool);
+        let url = url.clone();
+        let output_path = output_path.to_string();
+        let scheduler = Arc::clone(&scheduler);
+        let sleep_tracker = Arc::clone(&sleep_tracker);
+        let tracker = Arc::clone(&tracker);
+        let pause_flag = Arc::clone(&pause_flag);
+        let etag = metadata_etag.clone();
+        let config = config.clone();
+
+        handles.push(thread::spawn(move || {
+            while let Some(block) = pool.next_block() {
+                if let Err(e) = download_block(&block, &url, &output_path, &scheduler, &sleep_tracker, &tracker, &pause_flag, &etag, &config) {
+                    eprintln!("❌ Block {} failed: {}", block.id, e);
+                }
+            }
+        }));
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    if pause_flag.load(Ordering::Relaxed) {
+        println!("💾 Saving metadata on pause...");
+        metadata.blocks = pool.snapshot_blocks();
+        metadata.save_to_file(&meta_path)?;
+        println!("⏸️ Work-stealing download paused.");
+        return Ok(());
+    }
+
+    fs::remove_file(&meta_path).ok();
+    println!("\n✅ Work-stealing download complete: {}", output_path);
+    Ok(())
+}
+
+/// Downloads one block's remaining bytes, retrying with backoff on
+/// transient failures. Stops early (without error) if another worker
+/// shrinks `block.cap` out from under it mid-stream — the stolen remainder
+/// is somebody else's block now.
+fn download_block(
+    block: &Arc<Block>,
+    url: &str,
+    output_path: &str,
+    scheduler: &Arc<Scheduler>,
+    sleep_tracker: &Arc<SleepTracker>,
+    tracker: &Arc<Mutex<SegmentedProgressTracker>>,
+    pause_flag: &Arc<AtomicBool>,
+    etag: &Option<String>,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for attempt in 1..=config.max_retries {
+        let start = block.start + block.downloaded.load(Ordering::SeqCst);
+        let end = block.cap.load(Ordering::SeqCst);
+        if start > end {
+            return Ok(());
+        }
+
+        let _token = scheduler.acquire();
+        let mut request = scheduler
+            .client()
+            .get(url)
+            .header(RANGE, format!("bytes={}-{}", start, end))
+            .header(USER_AGENT, "OpenDownloadManager/0.1");
+        if let Some(etag) = etag {
+            request = request.header(IF_RANGE, etag);
+        }
+
+        let mut response = match request.send() {
+            Ok(res) if etag.is_some() && res.status().as_u16() == 200 => {
+                return Err(format!(
+                    "Block {} got 200 instead of 206 for an If-Range request — remote resource changed",
+                    block.id
+                )
+                .into());
+            }
+            Ok(res) if res.status().is_success() || res.status().as_u16() == 206 => res,
+            Ok(res) => match classify_status(res.status().as_u16()) {
+                Failure::Permanent(reason) => return Err(reason.into()),
+                Failure::Retriable => {
+                    drop(_token);
+                    back_off(sleep_tracker, config, block.id, attempt);
+                    continue;
+                }
+            },
+            Err(e) => {
+                eprintln!("❌ Block {} network error: {}", block.id, e);
+                drop(_token);
+                back_off(sleep_tracker, config, block.id, attempt);
+                continue;
+            }
+        };
+
+        let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+            let mut file = OpenOptions::new().write(true).open(output_path)?;
+            file.seek(SeekFrom::Start(start))?;
+
+            let mut buffer = [0u8; 8192];
+            let mut offset = start;
+            loop {
+                if pause_flag.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+
+                let cap_now = block.cap.load(Ordering::SeqCst);
+                if offset > cap_now {
+                    break;
+                }
+
+                let n = response.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+
+                let max_len = (cap_now - offset + 1) as usize;
+                let write_len = n.min(max_len);
+                file.write_all(&buffer[..write_len])?;
+                offset += write_len as u64;
+                block.downloaded.fetch_add(write_len as u64, Ordering::SeqCst);
+                tracker.lock().unwrap().update(block.id, write_len as u64);
+
+                if write_len < n {
+                    break;
+                }
+            }
+            Ok(())
+        })();
+
+        if result.is_ok() {
+            return Ok(());
+        }
+
+        eprintln!("❌ Block {} stream error: {}", block.id, result.unwrap_err());
+        drop(_token);
+        back_off(sleep_tracker, config, block.id, attempt);
+    }
+
+    Err(format!("Block {} failed after {} attempts", block.id, config.max_retries).into())
+}
+
+/// Sleeps (via the shared `SleepTracker`) for an exponential delay with
+/// +/-50% jitter before the next retry attempt.
+fn back_off(sleep_tracker: &SleepTracker, config: &Config, block_id: usize, attempt: u8) {
+    let base = config.retry_base_ms;
+    let cap = Duration::from_secs(config.retry_cap_secs);
+    let exp = base.saturating_mul(1u64 << attempt.saturating_sub(1).min(32));
+    let delay = Duration::from_millis(exp).min(cap);
+
+    let jitter = rand::thread_rng().gen_range(0.5..1.5);
+    let delay = delay.mul_f64(jitter);
+
+    println!("💤 Block {} backing off for {:.2}s before retry", block_id, delay.as_secs_f64());
+    sleep_tracker.sleep_until(block_id, delay);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: usize, start: u64, end: u64, downloaded: u64) -> Arc<Block> {
+        Arc::new(Block {
+            id,
+            start,
+            cap: AtomicU64::new(end),
+            downloaded: AtomicU64::new(downloaded),
+            claimed_for_steal: AtomicBool::new(false),
+        })
+    }
+
+    #[test]
+    fn try_steal_splits_remaining_range_in_half() {
+        let victim = block(0, 0, 999_999, 0);
+        let pool = WorkPool::new(vec![], 1);
+        pool.in_flight.lock().unwrap().push(Arc::clone(&victim));
+
+        let stolen = pool.try_steal().expect("steal should succeed on a large remaining range");
+
+        // Remaining was 1_000_000 bytes (0..=999_999); the stolen half starts
+        // at the midpoint and keeps the original end, while the victim's cap
+        // shrinks to just before it.
+        assert_eq!(stolen.start, 500_000);
+        assert_eq!(stolen.cap.load(Ordering::SeqCst), 999_999);
+        assert_eq!(victim.cap.load(Ordering::SeqCst), 499_999);
+        assert!(!victim.claimed_for_steal.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn try_steal_refuses_blocks_below_the_minimum() {
+        // Inclusive end: 0..=MIN_STEAL_BYTES - 2 is MIN_STEAL_BYTES - 1 bytes.
+        let victim = block(0, 0, MIN_STEAL_BYTES - 2, 0);
+        let pool = WorkPool::new(vec![], 1);
+        pool.in_flight.lock().unwrap().push(victim);
+
+        assert!(pool.try_steal().is_none());
+    }
+}
@@ -11,6 +11,14 @@ pub struct SegmentMetadata {
     pub part_path: String,
 }
 
+/// An expected digest to verify a completed download against.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum Checksum {
+    Sha256(String),
+    Sha1(String),
+    Md5(String),
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DownloadMetadata {
     pub url: String,
@@ -19,6 +27,17 @@ pub struct DownloadMetadata {
     pub etag: Option<String>,
     pub last_modified: Option<String>,
     pub segments: Vec<SegmentMetadata>,
+    /// Digest the assembled file must match once merged, if the caller asked
+    /// for end-to-end verification.
+    #[serde(default)]
+    pub expected_checksum: Option<Checksum>,
+    /// A `Digest`/`Content-MD5`-style header captured from the initial
+    /// response, normalized to `algo=value` (a bare `Content-MD5` is tagged
+    /// `md5=` at capture time). Verified against the merged output as a free
+    /// integrity check whenever the caller didn't request an explicit
+    /// `expected_checksum`.
+    #[serde(default)]
+    pub remote_digest: Option<String>,
 }
 
 impl DownloadMetadata {
@@ -33,6 +52,48 @@ impl DownloadMetadata {
         Ok(metadata)
     }
 
+    pub fn exists(path: &str) -> bool {
+        Path::new(path).exists()
+    }
+}
+
+/// One claimable unit of work in the work-stealing downloader. Unlike
+/// `SegmentMetadata`'s one-range-per-thread model, blocks are small, many,
+/// and `end` can shrink after the fact when a faster worker steals the
+/// unfinished tail of a block another worker is still downloading.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockMetadata {
+    pub block_id: usize,
+    pub start: u64,
+    pub end: u64,
+    pub downloaded: u64,
+}
+
+/// Persisted state for `download_file_work_stealing`, analogous to
+/// `DownloadMetadata` but tracking the work-stealing block pool instead of
+/// a fixed per-thread segment list.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkStealingMetadata {
+    pub url: String,
+    pub output_path: String,
+    pub total_size: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub blocks: Vec<BlockMetadata>,
+}
+
+impl WorkStealingMetadata {
+    pub fn save_to_file(&self, path: &str) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load_from_file(path: &str) -> std::io::Result<WorkStealingMetadata> {
+        let contents = fs::read_to_string(path)?;
+        let metadata: WorkStealingMetadata = serde_json::from_str(&contents)?;
+        Ok(metadata)
+    }
+
     pub fn exists(path: &str) -> bool {
         Path::new(path).exists()
     }
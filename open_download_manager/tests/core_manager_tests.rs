@@ -21,6 +21,11 @@ fn test_full_download_flow() {
         max_retries: 2,
         num_threads: 4,
         default_output_path: "".into(),
+        retry_base_ms: 500,
+        retry_cap_secs: 30,
+        max_concurrent: 16,
+        archive_kind_override: None,
+        block_size_bytes: 4 * 1024 * 1024,
     };
 
     let manager = DownloadManager::new(config);